@@ -0,0 +1,224 @@
+// IEC 60870-5-104 APDU 解码
+
+/// APDU 解码过程中的错误
+#[derive(Debug, Clone, PartialEq)]
+pub enum Iec104Error {
+    /// 帧太短，无法读取对应字段
+    Truncated,
+    /// 起始字节不是 0x68
+    BadStartByte(u8),
+    /// 长度字段与实际剩余字节数不一致
+    LengthMismatch { declared: usize, actual: usize },
+    /// 含多个信息对象但其元素长度尚未建模的类型，无法切分
+    UnsupportedType { type_id: u8 },
+}
+
+/// APCI 的三种帧格式
+#[derive(Debug, Clone, PartialEq)]
+pub enum Apdu {
+    /// I 格式：携带 ASDU 的信息传输帧
+    IFormat {
+        send_seq: u16,
+        recv_seq: u16,
+        asdu: Asdu,
+    },
+    /// S 格式：只携带接收序号的监视帧
+    SFormat { recv_seq: u16 },
+    /// U 格式：控制帧（STARTDT/STOPDT/TESTFR）
+    UFormat { function: UControl },
+}
+
+/// U 格式控制功能，对应控制域的置位
+#[derive(Debug, Clone, PartialEq)]
+pub enum UControl {
+    StartDtAct,
+    StartDtCon,
+    StopDtAct,
+    StopDtCon,
+    TestFrAct,
+    TestFrCon,
+    /// 未识别的控制位组合
+    Unknown(u8),
+}
+
+/// I 格式帧携带的应用服务数据单元
+#[derive(Debug, Clone, PartialEq)]
+pub struct Asdu {
+    pub type_id: u8,
+    /// 可变结构限定词的 SQ 位
+    pub sequence: bool,
+    /// 信息对象数目
+    pub count: u8,
+    pub cause: u8,
+    /// 传送原因的 T 位（测试）
+    pub test: bool,
+    /// 传送原因的 P/N 位（肯定/否定确认）
+    pub negative: bool,
+    pub originator: u8,
+    pub common_address: u16,
+    pub objects: Vec<InformationObject>,
+}
+
+/// 一个信息对象：3 字节信息对象地址加上类型相关的元素
+#[derive(Debug, Clone, PartialEq)]
+pub struct InformationObject {
+    pub address: u32,
+    pub element: InformationElement,
+}
+
+/// 信息对象中的类型相关元素
+#[derive(Debug, Clone, PartialEq)]
+pub enum InformationElement {
+    /// 类型 13：短浮点数测量值（IEEE-754 小端）加品质描述词
+    ShortFloat { value: f32, quality: u8 },
+    /// 其它尚未建模的类型，保留原始字节
+    Raw(Vec<u8>),
+}
+
+const START_BYTE: u8 = 0x68;
+
+/// 解码一个完整的 104 报文帧
+pub fn decode_apdu(frame: &[u8]) -> Result<Apdu, Iec104Error> {
+    if frame.len() < 6 {
+        return Err(Iec104Error::Truncated);
+    }
+    if frame[0] != START_BYTE {
+        return Err(Iec104Error::BadStartByte(frame[0]));
+    }
+
+    let length = frame[1] as usize;
+    let body = &frame[2..];
+    if body.len() != length {
+        return Err(Iec104Error::LengthMismatch {
+            declared: length,
+            actual: body.len(),
+        });
+    }
+
+    let control = [body[0], body[1], body[2], body[3]];
+    let payload = &body[4..];
+
+    // 控制域第一个八位位组的低两位决定帧格式
+    if control[0] & 0x01 == 0 {
+        let send_seq = (control[0] as u16 >> 1) | ((control[1] as u16) << 7);
+        let recv_seq = (control[2] as u16 >> 1) | ((control[3] as u16) << 7);
+        let asdu = decode_asdu(payload)?;
+        Ok(Apdu::IFormat {
+            send_seq,
+            recv_seq,
+            asdu,
+        })
+    } else if control[0] & 0x03 == 0x01 {
+        let recv_seq = (control[2] as u16 >> 1) | ((control[3] as u16) << 7);
+        Ok(Apdu::SFormat { recv_seq })
+    } else {
+        let function = match control[0] & 0xfc {
+            0x04 => UControl::StartDtAct,
+            0x08 => UControl::StartDtCon,
+            0x10 => UControl::StopDtAct,
+            0x20 => UControl::StopDtCon,
+            0x40 => UControl::TestFrAct,
+            0x80 => UControl::TestFrCon,
+            other => UControl::Unknown(other),
+        };
+        Ok(Apdu::UFormat { function })
+    }
+}
+
+fn decode_asdu(input: &[u8]) -> Result<Asdu, Iec104Error> {
+    if input.len() < 6 {
+        return Err(Iec104Error::Truncated);
+    }
+    let type_id = input[0];
+    let vsq = input[1];
+    let sequence = vsq & 0x80 != 0;
+    let count = vsq & 0x7f;
+    let cause = input[2] & 0x3f;
+    let test = input[2] & 0x80 != 0;
+    let negative = input[2] & 0x40 != 0;
+    let originator = input[3];
+    let common_address = input[4] as u16 | ((input[5] as u16) << 8);
+
+    let mut rest = &input[6..];
+    let mut objects = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        if rest.len() < 3 {
+            return Err(Iec104Error::Truncated);
+        }
+        let address = rest[0] as u32 | ((rest[1] as u32) << 8) | ((rest[2] as u32) << 16);
+        rest = &rest[3..];
+        let element = decode_element(type_id, count, &mut rest)?;
+        objects.push(InformationObject { address, element });
+    }
+
+    Ok(Asdu {
+        type_id,
+        sequence,
+        count,
+        cause,
+        test,
+        negative,
+        originator,
+        common_address,
+        objects,
+    })
+}
+
+fn decode_element(
+    type_id: u8,
+    count: u8,
+    rest: &mut &[u8],
+) -> Result<InformationElement, Iec104Error> {
+    match type_id {
+        // M_ME_NC_1：短浮点数测量值
+        13 => {
+            if rest.len() < 5 {
+                return Err(Iec104Error::Truncated);
+            }
+            let value = f32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]);
+            let quality = rest[4];
+            *rest = &rest[5..];
+            Ok(InformationElement::ShortFloat { value, quality })
+        }
+        // 未建模的类型：元素长度未知，无法在多对象帧中切分，
+        // 仅当只有单个信息对象时把剩余字节整体作为原始内容保留
+        _ => {
+            if count > 1 {
+                return Err(Iec104Error::UnsupportedType { type_id });
+            }
+            let raw = rest.to_vec();
+            *rest = &rest[rest.len()..];
+            Ok(InformationElement::Raw(raw))
+        }
+    }
+}
+
+#[test]
+fn test_decode_iformat_short_float() {
+    let frame = hex::decode(
+        "6822eee05c460d03030001001940000080c843001a40003373c843001b400033b3c84300",
+    )
+    .unwrap();
+    let apdu = decode_apdu(&frame).unwrap();
+    match apdu {
+        Apdu::IFormat {
+            send_seq,
+            recv_seq,
+            asdu,
+        } => {
+            assert_eq!(send_seq, 28791);
+            assert_eq!(recv_seq, 9006);
+            assert_eq!(asdu.type_id, 13);
+            assert_eq!(asdu.count, 3);
+            assert_eq!(asdu.cause, 3);
+            assert_eq!(asdu.common_address, 1);
+            assert_eq!(asdu.objects.len(), 3);
+            assert_eq!(asdu.objects[0].address, 16409);
+            match asdu.objects[2].element {
+                InformationElement::ShortFloat { quality, .. } => assert_eq!(quality, 0),
+                _ => panic!("expected short float"),
+            }
+        }
+        other => panic!("expected I-format, got {:?}", other),
+    }
+}
@@ -0,0 +1,68 @@
+// MQTT 遥测 JSON 载荷的结构化解析
+
+use serde_json::Value;
+use crate::parser::parser::str_as_unix_time;
+
+/// MQTT 载荷解析错误
+#[derive(Debug, Clone, PartialEq)]
+pub enum MqttError {
+    /// JSON 反序列化失败
+    Json,
+    /// JSON 结构不符合预期（缺少 `images`/`tags` 等字段）
+    Structure,
+}
+
+/// 从嵌套遥测 JSON 中抽平出来的一条测点采样
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagSample {
+    pub name: String,
+    pub value: f64,
+    /// 采样时刻，取自所在 image 的 `t` 字段
+    pub point_time: i64,
+}
+
+/// 把形如 `{"images":[{"t":..., "tags":{...}}]}` 的载荷解析为一组命名采样。
+/// 每个 image 的 `t` 经 [`str_as_unix_time`] 作为采样时刻，`tags` 中每个数值型的键被抽平；
+/// 值不是数值的键被跳过。
+pub fn parse_mqtt_payload(payload: &str) -> Result<Vec<TagSample>, MqttError> {
+    let root: Value = serde_json::from_str(payload).map_err(|_| MqttError::Json)?;
+    let images = root
+        .get("images")
+        .and_then(Value::as_array)
+        .ok_or(MqttError::Structure)?;
+
+    let mut samples = Vec::new();
+    for image in images {
+        let point_time = image
+            .get("t")
+            .and_then(Value::as_str)
+            .map(str_as_unix_time)
+            .ok_or(MqttError::Structure)?;
+        let tags = image
+            .get("tags")
+            .and_then(Value::as_object)
+            .ok_or(MqttError::Structure)?;
+        for (name, value) in tags {
+            if let Some(value) = value.as_f64() {
+                samples.push(TagSample {
+                    name: name.clone(),
+                    value,
+                    point_time,
+                });
+            }
+        }
+    }
+
+    Ok(samples)
+}
+
+#[test]
+fn test_parse_mqtt_payload() {
+    let payload = r#"{"ver":211,"images":[{"t":"2024-05-05 00:00:19.009","tags":{"BMS_pack_2_ele_u":672.4,"BMS_pack_IoStatus":1}}]}"#;
+    let samples = parse_mqtt_payload(payload).unwrap();
+    assert_eq!(samples.len(), 2);
+    let point_time = str_as_unix_time("2024-05-05 00:00:19.009");
+    assert!(samples.iter().all(|s| s.point_time == point_time));
+    let u = samples.iter().find(|s| s.name == "BMS_pack_2_ele_u").unwrap();
+    assert_eq!(u.value, 672.4);
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+use std::io::BufRead;
 use nom::branch::alt;
 use nom::bytes::complete::tag;
 use nom::character::complete::{digit1, newline, not_line_ending, space1};
@@ -26,10 +28,49 @@ pub struct NetworkInfo {
 
 }
 
+/// 时间戳无法解析时的处理策略
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorPolicy {
+    /// 回退为纪元起点 0
+    Zero,
+    /// 传播 nom 错误，让读取器重新同步
+    Skip,
+    /// 回退为指定的固定值
+    Passthrough(i64),
+}
+
+/// 时间戳解析策略：日志时间所假定的时区，以及解析失败时的行为
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeConfig {
+    pub assumed_tz: FixedOffset,
+    pub on_parse_error: ErrorPolicy,
+}
+
+impl Default for TimeConfig {
+    fn default() -> Self {
+        // 既有行为：日志时间按东八区（中国）解读，不可解析时回退为 0
+        TimeConfig {
+            assumed_tz: FixedOffset::east_opt(8 * 3600).unwrap(),
+            on_parse_error: ErrorPolicy::Zero,
+        }
+    }
+}
+
+/// 按给定配置把时间字符串转成毫秒级 Unix 时间戳。
+/// 解析失败时依 [`ErrorPolicy`] 返回 `Some`（回退）或 `None`（`Skip`）。
+pub fn str_as_unix_time_with(server_time: &str, config: &TimeConfig) -> Option<i64> {
+    match parse_with_timezone(server_time, &config.assumed_tz) {
+        Ok(datetime) => Some(datetime.timestamp_millis()),
+        Err(_) => match config.on_parse_error {
+            ErrorPolicy::Zero => Some(0),
+            ErrorPolicy::Skip => None,
+            ErrorPolicy::Passthrough(value) => Some(value),
+        },
+    }
+}
+
 pub fn str_as_unix_time(server_time: &str) -> i64 {
-    parse_with_timezone(server_time, &FixedOffset::west_opt(0).unwrap())
-        .map(|x| x.timestamp_millis() + (-8 * 3600 * 1000i64))
-        .unwrap_or(0i64)
+    str_as_unix_time_with(server_time, &TimeConfig::default()).unwrap_or(0)
 }
 
 pub fn bytes_to_uint8(array: &[u8]) -> Option<u8> {
@@ -42,6 +83,11 @@ pub fn bytes_to_uint8(array: &[u8]) -> Option<u8> {
 
 // 解析服务器时间
 fn parse_server_time(input: &str) -> IResult<&str, i64> {
+    parse_server_time_with(&TimeConfig::default(), input)
+}
+
+// 按给定时间策略解析服务器时间；`ErrorPolicy::Skip` 时返回 nom 错误
+fn parse_server_time_with<'a>(config: &TimeConfig, input: &'a str) -> IResult<&'a str, i64> {
     let mut parser = tuple((
         separated_list1(tag("-"), digit1),
         space1,
@@ -52,8 +98,13 @@ fn parse_server_time(input: &str) -> IResult<&str, i64> {
 
     let (input, (date, _, time, _, micro_seconds)) = parser(input)?;
     let datetime = format!("{} {}.{}", date.join("-"), time.join(":"), micro_seconds);
-    let unix_time = str_as_unix_time(&datetime);
-    Ok((input, unix_time))
+    match str_as_unix_time_with(&datetime, config) {
+        Some(unix_time) => Ok((input, unix_time)),
+        None => Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Verify,
+        ))),
+    }
 }
 
 // 解析 IP 地址或域名
@@ -103,57 +154,348 @@ fn parse_network_info(input: &str) -> IResult<&str, NetworkInfo> {
     Ok((input, network_info))
 }
 
-fn parse_payload(input: &str) -> IResult<&str, &str> {
+fn parse_payload(input: &str) -> IResult<&str, (&str, &str)> {
     let mut parser = tuple((alt((tag("D:"), tag("R:"))), not_line_ending));
-    let (input, (_, json)) = parser(input)?;
-    Ok((input, json))
+    let (input, (prefix, json)) = parser(input)?;
+    Ok((input, (prefix, json)))
+}
+
+/// 某个解码器无法处理载荷时返回的错误
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    /// 十六进制载荷解码失败
+    Hex,
+}
+
+/// 一条解码后的消息：消息类型加上写入 `IotMessage.message` 的字节，
+/// 以及（MQTT 时）抽平后的命名采样，供时序下游直接摄取而无需二次解析 JSON
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedMessage {
+    pub message_type: String,
+    pub message: Vec<u8>,
+    pub samples: Option<Vec<crate::mqtt::TagSample>>,
+}
+
+/// 一条解析出的记录：协议消息本身，加上（MQTT 时）抽平后的命名采样。
+/// `IotMessage` 为 protobuf 生成类型无法承载 Rust 侧的 [`crate::mqtt::TagSample`]，
+/// 故在此一并返回，使时序下游无需二次解析 JSON。
+#[derive(Debug, Clone, PartialEq)]
+pub struct IotRecord {
+    pub message: IotMessage,
+    pub samples: Option<Vec<crate::mqtt::TagSample>>,
+}
+
+/// 一种字段协议的解码器。`detect` 判断本解码器是否适用于该载荷，可参考
+/// 网络信息、`D:`/`R:` 载荷前缀以及去前缀后的字节；`decode` 把载荷转成
+/// 写入消息的字节。
+pub trait ProtocolDecoder {
+    fn detect(&self, net: &NetworkInfo, prefix: &str, payload: &[u8]) -> bool;
+    fn decode(&self, net: &NetworkInfo, prefix: &str, payload: &[u8]) -> Result<DecodedMessage, DecodeError>;
+}
+
+/// MQTT-JSON：端口为 0 的通道，原样保留 JSON 字节
+pub struct MqttJsonDecoder;
+
+impl ProtocolDecoder for MqttJsonDecoder {
+    fn detect(&self, net: &NetworkInfo, _prefix: &str, _payload: &[u8]) -> bool {
+        net.client_port == 0
+    }
+
+    fn decode(&self, _net: &NetworkInfo, _prefix: &str, payload: &[u8]) -> Result<DecodedMessage, DecodeError> {
+        let samples = std::str::from_utf8(payload)
+            .ok()
+            .and_then(|text| crate::mqtt::parse_mqtt_payload(text).ok());
+        Ok(DecodedMessage {
+            message_type: "mqtt".into(),
+            message: payload.to_vec(),
+            samples,
+        })
+    }
+}
+
+/// IEC104-hex：端口非 0 的通道，载荷为十六进制编码的 104 报文
+pub struct Iec104HexDecoder;
+
+impl ProtocolDecoder for Iec104HexDecoder {
+    fn detect(&self, net: &NetworkInfo, _prefix: &str, _payload: &[u8]) -> bool {
+        net.client_port != 0
+    }
+
+    fn decode(&self, _net: &NetworkInfo, _prefix: &str, payload: &[u8]) -> Result<DecodedMessage, DecodeError> {
+        let message = hex::decode(payload).map_err(|_| DecodeError::Hex)?;
+        Ok(DecodedMessage {
+            message_type: "iec104".into(),
+            message,
+            samples: None,
+        })
+    }
 }
 
-pub fn parse_log(input: &str) -> IResult<&str, Option<IotMessage>> {
-    let mut parser = tuple((parse_server_time, space1, parse_network_info, space1, parse_payload, many0(newline)));
-    let (input, (ts, _, network_info, _, json_str, _)) = parser(input)?;
+/// 按注册顺序依次咨询各解码器的分发表
+pub struct ProtocolRegistry {
+    decoders: Vec<Box<dyn ProtocolDecoder>>,
+}
+
+impl ProtocolRegistry {
+    /// 不含任何解码器的空注册表
+    pub fn new() -> Self {
+        ProtocolRegistry { decoders: Vec::new() }
+    }
+
+    /// 预置 MQTT-JSON 与 IEC104-hex 两个内建解码器
+    pub fn with_defaults() -> Self {
+        let mut registry = ProtocolRegistry::new();
+        registry.register(Box::new(MqttJsonDecoder));
+        registry.register(Box::new(Iec104HexDecoder));
+        registry
+    }
+
+    /// 追加一个解码器；先注册的先被 `detect` 命中
+    pub fn register(&mut self, decoder: Box<dyn ProtocolDecoder>) {
+        self.decoders.push(decoder);
+    }
+
+    /// 用第一个 `detect` 命中的解码器解码载荷；无解码器命中时返回 `None`
+    pub fn decode(
+        &self,
+        net: &NetworkInfo,
+        prefix: &str,
+        payload: &[u8],
+    ) -> Option<Result<DecodedMessage, DecodeError>> {
+        self.decoders
+            .iter()
+            .find(|decoder| decoder.detect(net, prefix, payload))
+            .map(|decoder| decoder.decode(net, prefix, payload))
+    }
+}
+
+impl Default for ProtocolRegistry {
+    fn default() -> Self {
+        ProtocolRegistry::with_defaults()
+    }
+}
+
+pub fn parse_log(input: &str) -> IResult<&str, Option<IotRecord>> {
+    parse_log_with(&ProtocolRegistry::with_defaults(), input)
+}
+
+pub fn parse_log_with<'a>(
+    registry: &ProtocolRegistry,
+    input: &'a str,
+) -> IResult<&'a str, Option<IotRecord>> {
+    parse_log_with_config(registry, &TimeConfig::default(), input)
+}
+
+pub fn parse_log_with_config<'a>(
+    registry: &ProtocolRegistry,
+    time_config: &TimeConfig,
+    input: &'a str,
+) -> IResult<&'a str, Option<IotRecord>> {
+    let (input, ts) = parse_server_time_with(time_config, input)?;
+    let mut parser = tuple((space1, parse_network_info, space1, parse_payload, many0(newline)));
+    let (input, (_, network_info, _, (prefix, json_str), _)) = parser(input)?;
 
     let mut channel_info = ChannelInfo::default();
-    channel_info.client_ip = network_info.client_ip;
+    channel_info.client_ip = network_info.client_ip.clone();
     channel_info.client_port = network_info.client_port;
-    channel_info.server_ip = network_info.server_ip;
+    channel_info.server_ip = network_info.server_ip.clone();
     channel_info.server_port = network_info.server_port;
-    channel_info.protocol = network_info.protocol;
-    let message_type = match network_info.client_port {
-        0 => "mqtt",
-        _ => "iec104"
-    };
-
+    channel_info.protocol = network_info.protocol.clone();
 
-    if network_info.client_port != 0 {
-        if let Ok(message) = hex::decode(json_str) {
+    match registry.decode(&network_info, prefix, json_str.as_bytes()) {
+        Some(Ok(decoded)) => {
             let mut iot_message = IotMessage::default();
             iot_message.channel = channel_info;
-
-            iot_message.message_type = Some(message_type.into());
-            iot_message.message = message;
+            iot_message.message_type = Some(decoded.message_type);
+            iot_message.message = decoded.message;
             iot_message.server_time = Some(ts);
-            Ok((input, Some(iot_message)))
-        } else {
-            Ok((input, None))
+            Ok((input, Some(IotRecord {
+                message: iot_message,
+                samples: decoded.samples,
+            })))
         }
-    } else {
-        let mut iot_message = IotMessage::default();
-        iot_message.channel = channel_info;
+        _ => Ok((input, None)),
+    }
+}
+
+/// 读取一条日志记录失败时的原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// 以时间戳开头但无法解析为一条记录
+    Malformed(String),
+    /// 载荷解码失败（如十六进制）
+    Decode(String),
+}
+
+/// 遍历整个捕获文件后累计的统计信息，供大文件分流排查
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ParseStats {
+    /// 读取的总行数（不含续行噪声）
+    pub total_lines: usize,
+    /// 按 `message_type` 统计的成功解码数
+    pub decoded: HashMap<String, usize>,
+    /// 十六进制解码失败次数
+    pub hex_decode_failures: usize,
+    /// 时间戳解析失败次数
+    pub timestamp_parse_failures: usize,
+}
+
+/// 流式读取多条日志记录。逐行消费任意 `impl BufRead`，单行失败时
+/// 重新同步到下一条以时间戳开头的记录而不中断整个流，并累计
+/// [`ParseStats`] 以便分流。
+pub struct LogReader<R> {
+    reader: R,
+    registry: ProtocolRegistry,
+    time_config: TimeConfig,
+    batch_size: usize,
+    stats: ParseStats,
+}
+
+impl<R: BufRead> LogReader<R> {
+    /// 使用内建解码器与默认批大小创建读取器
+    pub fn new(reader: R) -> Self {
+        LogReader {
+            reader,
+            registry: ProtocolRegistry::with_defaults(),
+            time_config: reader_time_config(),
+            batch_size: 1024,
+            stats: ParseStats::default(),
+        }
+    }
+
+    /// 使用自定义协议注册表创建读取器
+    pub fn with_registry(reader: R, registry: ProtocolRegistry) -> Self {
+        LogReader {
+            reader,
+            registry,
+            time_config: reader_time_config(),
+            batch_size: 1024,
+            stats: ParseStats::default(),
+        }
+    }
 
-        iot_message.message_type = Some(message_type.into());
-        iot_message.message = json_str.to_string().into_bytes();
-        iot_message.server_time = Some(ts);
-        Ok((input, Some(iot_message)))
+    /// 设置时间戳解析策略（时区与解析失败行为）
+    pub fn time_config(mut self, time_config: TimeConfig) -> Self {
+        self.time_config = time_config;
+        self
+    }
+
+    /// 设置单个批次产出的最大记录数，使多 GB 日志的内存保持平稳
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// 读取下一批记录，至多 `batch_size` 条；返回空 Vec 表示已到达文件末尾
+    pub fn next_batch(&mut self) -> Vec<Result<IotRecord, ParseError>> {
+        let mut batch = Vec::with_capacity(self.batch_size);
+        while batch.len() < self.batch_size {
+            match self.next() {
+                Some(item) => batch.push(item),
+                None => break,
+            }
+        }
+        batch
+    }
+
+    /// 读取结束后返回累计统计
+    pub fn stats(&self) -> &ParseStats {
+        &self.stats
     }
 }
 
+/// 读取器默认的时间策略：时间戳不可解析时传播错误（`Skip`），
+/// 使其被如实计入 [`ParseStats::timestamp_parse_failures`] 而非静默回退为 0
+fn reader_time_config() -> TimeConfig {
+    TimeConfig {
+        on_parse_error: ErrorPolicy::Skip,
+        ..TimeConfig::default()
+    }
+}
+
+/// 判断一行是否以 `YYYY-` 形式的时间戳开头
+fn starts_with_timestamp(line: &str) -> bool {
+    let bytes = line.as_bytes();
+    bytes.len() > 4 && bytes[..4].iter().all(|b| b.is_ascii_digit()) && bytes[4] == b'-'
+}
+
+impl<R: BufRead> Iterator for LogReader<R> {
+    type Item = Result<IotRecord, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(_) => return None,
+            }
+
+            let trimmed = line.trim_end_matches(['\r', '\n']);
+            // 重新同步：跳过不以时间戳开头的续行噪声
+            if !starts_with_timestamp(trimmed) {
+                continue;
+            }
+
+            self.stats.total_lines += 1;
+            // 先单独解析时间戳，把真正的时间戳解析失败与其后的结构性失败区分开
+            if parse_server_time_with(&self.time_config, trimmed).is_err() {
+                self.stats.timestamp_parse_failures += 1;
+                return Some(Err(ParseError::Malformed(trimmed.to_string())));
+            }
+            return match parse_log_with_config(&self.registry, &self.time_config, trimmed) {
+                Ok((_, Some(record))) => {
+                    if let Some(message_type) = &record.message.message_type {
+                        *self.stats.decoded.entry(message_type.clone()).or_insert(0) += 1;
+                    }
+                    Some(Ok(record))
+                }
+                Ok((_, None)) => {
+                    self.stats.hex_decode_failures += 1;
+                    Some(Err(ParseError::Decode(trimmed.to_string())))
+                }
+                Err(_) => Some(Err(ParseError::Malformed(trimmed.to_string()))),
+            };
+        }
+    }
+}
+
+#[test]
+fn test_log_reader_recovers_and_counts() {
+    let input = concat!(
+        "2024-05-05 23:59:58.846  [223.104.43.11:11686#10.0.1.88:5003] R:6822eee05c460d03030001001940000080c843001a40003373c843001b400033b3c84300\n",
+        "garbage continuation line that is not an entry\n",
+        "2024-05-05 23:59:59.000  [223.104.43.11:11686#10.0.1.88:5003] R:zzzz\n",
+    );
+    let mut reader = LogReader::new(std::io::Cursor::new(input));
+    let items: Vec<_> = reader.by_ref().collect();
+    assert_eq!(items.len(), 2);
+    assert!(items[0].is_ok());
+    assert!(matches!(items[1], Err(ParseError::Decode(_))));
+    assert_eq!(reader.stats().total_lines, 2);
+    assert_eq!(reader.stats().hex_decode_failures, 1);
+    assert_eq!(reader.stats().decoded.get("iec104"), Some(&1));
+}
+
 #[test]
 fn test_server_time() {
     let input = "2024-05-05 00:00:21.525";
     assert_eq!(parse_server_time(input), Ok(("", 1714838421525)));
 }
 
+#[test]
+fn test_time_config_error_policy() {
+    let zero = TimeConfig::default();
+    assert_eq!(str_as_unix_time_with("not a date", &zero), Some(0));
+
+    let skip = TimeConfig { on_parse_error: ErrorPolicy::Skip, ..TimeConfig::default() };
+    assert_eq!(str_as_unix_time_with("not a date", &skip), None);
+
+    let passthrough = TimeConfig { on_parse_error: ErrorPolicy::Passthrough(-1), ..TimeConfig::default() };
+    assert_eq!(str_as_unix_time_with("not a date", &passthrough), Some(-1));
+}
+
 #[test]
 fn test_iec104_network() {
     let input = "[223.104.43.11:11686#10.0.1.88:5003]";